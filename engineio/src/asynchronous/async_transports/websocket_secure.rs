@@ -1,35 +1,67 @@
 use std::fmt::Debug;
 use std::sync::Arc;
 
+use crate::asynchronous::proxy::Proxy;
+use crate::asynchronous::runtime::{connect, connect_via_proxy, Connector, Lock};
 use crate::asynchronous::transport::AsyncTransport;
 use crate::error::Result;
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures_util::StreamExt;
 use http::HeaderMap;
+#[cfg(feature = "native-tls")]
 use native_tls::TlsConnector;
-use tokio::sync::RwLock;
-use tokio_tungstenite::connect_async_tls_with_config;
-use tokio_tungstenite::Connector;
+#[cfg(feature = "rustls-tls")]
+use std::sync::Arc as StdArc;
+use tungstenite::protocol::WebSocketConfig;
 use url::Url;
 
 use super::websocket_general::AsyncWebsocketGeneralTransport;
 
+/// The TLS backend used to secure the websocket connection.
+///
+/// Mirrors the two connectors `tokio-tungstenite` supports, letting callers opt into a
+/// pure-Rust TLS stack instead of linking OpenSSL.
+pub enum TlsConfig {
+    /// Use `native-tls` (OpenSSL, or the platform TLS stack) as configured by the connector.
+    #[cfg(feature = "native-tls")]
+    NativeTls(TlsConnector),
+    /// Use `rustls` as configured by the supplied client config.
+    #[cfg(feature = "rustls-tls")]
+    Rustls(StdArc<rustls::ClientConfig>),
+}
+
+impl From<TlsConfig> for Connector {
+    fn from(tls_config: TlsConfig) -> Self {
+        match tls_config {
+            #[cfg(feature = "native-tls")]
+            TlsConfig::NativeTls(connector) => Connector::NativeTls(connector),
+            #[cfg(feature = "rustls-tls")]
+            TlsConfig::Rustls(client_config) => Connector::Rustls(client_config),
+        }
+    }
+}
+
 /// An asynchronous websocket transport type.
 /// This type only allows for secure websocket
 /// connections ("wss://").
 pub struct WebsocketSecureTransport {
     inner: AsyncWebsocketGeneralTransport,
-    base_url: Arc<RwLock<Url>>,
+    base_url: Arc<Lock<Url>>,
 }
 
 impl WebsocketSecureTransport {
     /// Creates a new instance over a request that might hold additional headers, a possible
-    /// Tls connector and an URL.
+    /// Tls connector and an URL. An optional `WebSocketConfig` can be supplied to tune frame
+    /// and message size limits as well as write-buffer sizing for the underlying connection.
+    /// If a `Proxy` is given, the handshake is tunnelled through it (HTTP `CONNECT` or
+    /// SOCKS5) instead of dialing the host directly.
     pub(crate) async fn new(
         base_url: Url,
-        tls_config: Option<TlsConnector>,
+        tls_config: Option<TlsConfig>,
         headers: Option<HeaderMap>,
+        websocket_config: Option<WebSocketConfig>,
+        proxy: Option<Proxy>,
     ) -> Result<Self> {
         let mut url = base_url;
         url.query_pairs_mut().append_pair("transport", "websocket");
@@ -41,19 +73,38 @@ impl WebsocketSecureTransport {
             req.headers_mut().unwrap().extend(map);
         }
 
-        let (ws_stream, _) = connect_async_tls_with_config(
-            req.body(())?,
-            None,
-            tls_config.map(Connector::NativeTls),
-        )
-        .await?;
+        let ws_stream = match proxy {
+            Some(proxy) => {
+                let target_host = url.host_str().ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "url has no host")
+                })?;
+                let target_port = url.port_or_known_default().unwrap_or(443);
+                connect_via_proxy(
+                    req.body(())?,
+                    &proxy,
+                    target_host,
+                    target_port,
+                    websocket_config,
+                    tls_config.map(Connector::from),
+                )
+                .await?
+            }
+            None => {
+                connect(
+                    req.body(())?,
+                    websocket_config,
+                    tls_config.map(Connector::from),
+                )
+                .await?
+            }
+        };
 
         let (sen, rec) = ws_stream.split();
         let inner = AsyncWebsocketGeneralTransport::new(sen, rec).await;
 
         Ok(WebsocketSecureTransport {
             inner,
-            base_url: Arc::new(RwLock::new(url)),
+            base_url: Arc::new(Lock::new(url)),
         })
     }
 
@@ -0,0 +1,573 @@
+use crate::error::Result;
+use std::io::{Error as IoError, ErrorKind};
+use url::Url;
+
+#[cfg(feature = "tokio-runtime")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+#[cfg(feature = "tokio-runtime")]
+use tokio::net::TcpStream;
+
+#[cfg(all(feature = "async-std-runtime", not(feature = "tokio-runtime")))]
+use async_std::io::{
+    Read as AsyncRead, ReadExt as AsyncReadExt, Write as AsyncWrite, WriteExt as AsyncWriteExt,
+};
+#[cfg(all(feature = "async-std-runtime", not(feature = "tokio-runtime")))]
+use async_std::net::TcpStream;
+
+#[cfg(all(
+    feature = "smol-runtime",
+    not(feature = "tokio-runtime"),
+    not(feature = "async-std-runtime")
+))]
+use smol::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+#[cfg(all(
+    feature = "smol-runtime",
+    not(feature = "tokio-runtime"),
+    not(feature = "async-std-runtime")
+))]
+use smol::net::TcpStream;
+
+/// The size past which this client gives up reading an HTTP `CONNECT` response header while
+/// looking for the end of the header block, to bound memory use against a misbehaving proxy.
+/// The check runs after each 512-byte read, so the buffer can exceed it by up to 512 bytes.
+const MAX_CONNECT_RESPONSE_BYTES: usize = 8 * 1024;
+
+/// A proxy to route the websocket handshake through instead of dialing the target host
+/// directly. Supports HTTP `CONNECT` proxies as well as SOCKS5, selected by `url`'s scheme
+/// (`http://`/`https://` for the former, `socks5://` for the latter).
+#[derive(Clone)]
+pub struct Proxy {
+    /// The address of the proxy server.
+    pub url: Url,
+    /// Optional `username`/`password` credentials presented to the proxy.
+    pub auth: Option<(String, String)>,
+}
+
+impl std::fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Proxy")
+            .field("url", &self.url)
+            .field("auth", &self.auth.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl Proxy {
+    /// Dials `target_host:target_port` through this proxy and returns the established TCP
+    /// stream, ready to be wrapped in TLS and upgraded to a websocket connection.
+    pub(crate) async fn connect(&self, target_host: &str, target_port: u16) -> Result<TcpStream> {
+        let proxy_host = self
+            .url
+            .host_str()
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "proxy url has no host"))?;
+        let proxy_port = self.url.port_or_known_default().unwrap_or(1080);
+        let mut stream = TcpStream::connect((proxy_host, proxy_port)).await?;
+
+        match self.url.scheme() {
+            "socks5" | "socks5h" => {
+                self.connect_socks5(&mut stream, target_host, target_port)
+                    .await?
+            }
+            _ => {
+                self.connect_http(&mut stream, target_host, target_port)
+                    .await?
+            }
+        }
+
+        Ok(stream)
+    }
+
+    /// Negotiates an HTTP `CONNECT` tunnel to `target_host:target_port` over `stream`.
+    async fn connect_http<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: &mut S,
+        target_host: &str,
+        target_port: u16,
+    ) -> Result<()> {
+        let mut request = format!(
+            "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n",
+            host = target_host,
+            port = target_port
+        );
+        if let Some((user, pass)) = &self.auth {
+            let credentials = base64_encode(format!("{user}:{pass}").as_bytes());
+            request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+        }
+        request.push_str("\r\n");
+
+        stream.write_all(request.as_bytes()).await?;
+
+        let response = read_connect_response(stream).await?;
+        parse_connect_response(&response)
+    }
+
+    /// Negotiates a SOCKS5 tunnel (with optional username/password auth) to
+    /// `target_host:target_port` over `stream`, per RFC 1928/1929.
+    async fn connect_socks5<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: &mut S,
+        target_host: &str,
+        target_port: u16,
+    ) -> Result<()> {
+        validate_socks5_host(target_host)?;
+
+        let methods: &[u8] = if self.auth.is_some() {
+            &[0x00, 0x02]
+        } else {
+            &[0x00]
+        };
+        stream.write_all(&[0x05, methods.len() as u8]).await?;
+        stream.write_all(methods).await?;
+
+        let mut method_reply = [0u8; 2];
+        stream.read_exact(&mut method_reply).await?;
+        if method_reply[0] != 0x05 {
+            return Err(
+                IoError::new(ErrorKind::InvalidData, "proxy is not a SOCKS5 server").into(),
+            );
+        }
+
+        match method_reply[1] {
+            0x00 => {}
+            0x02 => {
+                let (user, pass) = self.auth.as_ref().ok_or_else(|| {
+                    IoError::new(ErrorKind::InvalidInput, "proxy requires SOCKS5 credentials")
+                })?;
+                let mut creds = vec![0x01, user.len() as u8];
+                creds.extend_from_slice(user.as_bytes());
+                creds.push(pass.len() as u8);
+                creds.extend_from_slice(pass.as_bytes());
+                stream.write_all(&creds).await?;
+
+                let mut auth_reply = [0u8; 2];
+                stream.read_exact(&mut auth_reply).await?;
+                if auth_reply[1] != 0x00 {
+                    return Err(IoError::new(
+                        ErrorKind::PermissionDenied,
+                        "SOCKS5 authentication failed",
+                    )
+                    .into());
+                }
+            }
+            _ => {
+                return Err(IoError::new(
+                    ErrorKind::Unsupported,
+                    "no acceptable SOCKS5 authentication method",
+                )
+                .into())
+            }
+        }
+
+        // Validated by `validate_socks5_host` above: RFC 1928 caps a domain name at 255 bytes.
+        let mut request = vec![0x05, 0x01, 0x00, 0x03, target_host.len() as u8];
+        request.extend_from_slice(target_host.as_bytes());
+        request.extend_from_slice(&target_port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply_head = [0u8; 4];
+        stream.read_exact(&mut reply_head).await?;
+        if reply_head[1] != 0x00 {
+            return Err(IoError::new(
+                ErrorKind::ConnectionRefused,
+                format!("SOCKS5 CONNECT failed with code {}", reply_head[1]),
+            )
+            .into());
+        }
+
+        // Discard the bound address the proxy echoes back (IPv4 / domain / IPv6 + port).
+        let remaining = match reply_head[3] {
+            0x01 => 4 + 2,
+            0x04 => 16 + 2,
+            0x03 => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len).await?;
+                len[0] as usize + 2
+            }
+            _ => {
+                return Err(
+                    IoError::new(ErrorKind::InvalidData, "unknown SOCKS5 address type").into(),
+                )
+            }
+        };
+        let mut discard = vec![0u8; remaining];
+        stream.read_exact(&mut discard).await?;
+
+        Ok(())
+    }
+}
+
+/// Checks that `host` fits in a SOCKS5 domain-name address (RFC 1928: a one-byte length
+/// prefix, so at most 255 bytes), instead of silently truncating it in the request.
+fn validate_socks5_host(host: &str) -> Result<()> {
+    if host.len() > u8::MAX as usize {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "host name '{host}' is {} bytes, which exceeds the 255-byte SOCKS5 limit",
+                host.len()
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Reads from `stream` until the end of the HTTP header block (`\r\n\r\n`) is seen, handling
+/// a response that arrives across multiple reads rather than assuming it fits in one packet.
+async fn read_connect_response<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>> {
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 512];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(IoError::new(
+                ErrorKind::UnexpectedEof,
+                "proxy closed the connection before completing the CONNECT response",
+            )
+            .into());
+        }
+        response.extend_from_slice(&chunk[..n]);
+
+        if response.windows(4).any(|w| w == b"\r\n\r\n") {
+            return Ok(response);
+        }
+        if response.len() > MAX_CONNECT_RESPONSE_BYTES {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "proxy CONNECT response header exceeded the size limit",
+            )
+            .into());
+        }
+    }
+}
+
+/// Parses the status line of a buffered HTTP `CONNECT` response, accepting only a `200`.
+fn parse_connect_response(response: &[u8]) -> Result<()> {
+    let text = String::from_utf8_lossy(response);
+    let status_line = text.lines().next().unwrap_or("");
+    if status_line.starts_with("HTTP/1.1 200") || status_line.starts_with("HTTP/1.0 200") {
+        Ok(())
+    } else {
+        Err(IoError::new(
+            ErrorKind::ConnectionRefused,
+            format!("proxy CONNECT failed: {status_line}"),
+        )
+        .into())
+    }
+}
+
+/// Minimal standard base64 encoder, used only for the `Proxy-Authorization` header so this
+/// module doesn't need an extra dependency for a handful of bytes.
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((input.len() + 2) / 3 * 4);
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = *chunk.get(1).unwrap_or(&0);
+        let b2 = *chunk.get(2).unwrap_or(&0);
+
+        out.push(ALPHABET[(b0 >> 2) as usize] as char);
+        out.push(ALPHABET[(((b0 & 0x03) << 4) | (b1 >> 4)) as usize] as char);
+        out.push(if chunk.len() > 1 {
+            ALPHABET[(((b1 & 0x0f) << 2) | (b2 >> 6)) as usize] as char
+        } else {
+            '='
+        });
+        out.push(if chunk.len() > 2 {
+            ALPHABET[(b2 & 0x3f) as usize] as char
+        } else {
+            '='
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_encode_matches_known_vectors() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode(b"user:pass"), "dXNlcjpwYXNz");
+    }
+
+    #[test]
+    fn parse_connect_response_accepts_200() {
+        assert!(parse_connect_response(b"HTTP/1.1 200 Connection established\r\n\r\n").is_ok());
+        assert!(parse_connect_response(b"HTTP/1.0 200 OK\r\n\r\n").is_ok());
+    }
+
+    #[test]
+    fn parse_connect_response_rejects_non_200() {
+        assert!(
+            parse_connect_response(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").is_err()
+        );
+        assert!(parse_connect_response(b"").is_err());
+    }
+
+    #[test]
+    fn validate_socks5_host_accepts_up_to_255_bytes() {
+        let host = "a".repeat(255);
+        assert!(validate_socks5_host(&host).is_ok());
+    }
+
+    #[test]
+    fn validate_socks5_host_rejects_over_255_bytes() {
+        let host = "a".repeat(256);
+        assert!(validate_socks5_host(&host).is_err());
+    }
+
+    #[test]
+    fn debug_redacts_proxy_auth() {
+        let proxy = Proxy {
+            url: Url::parse("http://proxy.example:8080").unwrap(),
+            auth: Some(("user".to_string(), "s3cret".to_string())),
+        };
+        let rendered = format!("{proxy:?}");
+        assert!(!rendered.contains("s3cret"));
+        assert!(rendered.contains("<redacted>"));
+    }
+}
+
+/// Wire-level tests for `connect_http`/`connect_socks5`, run over an in-memory duplex stream
+/// standing in for the TCP connection to the proxy.
+#[cfg(all(test, feature = "tokio-runtime"))]
+mod duplex_tests {
+    use super::*;
+
+    fn proxy(auth: Option<(&str, &str)>) -> Proxy {
+        Proxy {
+            url: Url::parse("http://proxy.example:8080").unwrap(),
+            auth: auth.map(|(user, pass)| (user.to_string(), pass.to_string())),
+        }
+    }
+
+    #[tokio::test]
+    async fn connect_http_sends_request_and_accepts_200() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let proxy = proxy(None);
+
+        let client_task = tokio::spawn(async move {
+            let mut client = client;
+            proxy.connect_http(&mut client, "example.com", 443).await
+        });
+
+        let mut buf = vec![0u8; 1024];
+        let n = server.read(&mut buf).await.unwrap();
+        let request = String::from_utf8_lossy(&buf[..n]).into_owned();
+        assert!(request.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+        assert!(request.contains("Host: example.com:443\r\n"));
+        assert!(request.ends_with("\r\n\r\n"));
+
+        server
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await
+            .unwrap();
+
+        client_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_http_includes_proxy_authorization_header() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let proxy = proxy(Some(("user", "pass")));
+
+        let client_task = tokio::spawn(async move {
+            let mut client = client;
+            proxy.connect_http(&mut client, "example.com", 443).await
+        });
+
+        let mut buf = vec![0u8; 1024];
+        let n = server.read(&mut buf).await.unwrap();
+        let request = String::from_utf8_lossy(&buf[..n]).into_owned();
+        assert!(request.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+
+        server
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await
+            .unwrap();
+
+        client_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_http_rejects_non_200_status() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let proxy = proxy(None);
+
+        let client_task = tokio::spawn(async move {
+            let mut client = client;
+            proxy.connect_http(&mut client, "example.com", 443).await
+        });
+
+        let mut buf = vec![0u8; 1024];
+        server.read(&mut buf).await.unwrap();
+        server
+            .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+            .await
+            .unwrap();
+
+        assert!(client_task.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn connect_http_handles_a_fragmented_response() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let proxy = proxy(None);
+
+        let client_task = tokio::spawn(async move {
+            let mut client = client;
+            proxy.connect_http(&mut client, "example.com", 443).await
+        });
+
+        let mut buf = vec![0u8; 1024];
+        server.read(&mut buf).await.unwrap();
+
+        // Dribble the response out one byte at a time to exercise the read loop.
+        for byte in b"HTTP/1.1 200 OK\r\n\r\n" {
+            server.write_all(&[*byte]).await.unwrap();
+        }
+
+        client_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_socks5_no_auth_handshake_sends_well_formed_frames() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let proxy = proxy(None);
+
+        let client_task = tokio::spawn(async move {
+            let mut client = client;
+            proxy.connect_socks5(&mut client, "example.com", 443).await
+        });
+
+        let mut greeting = [0u8; 2];
+        server.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [0x05, 0x01]);
+        let mut methods = vec![0u8; greeting[1] as usize];
+        server.read_exact(&mut methods).await.unwrap();
+        assert_eq!(methods, vec![0x00]);
+        server.write_all(&[0x05, 0x00]).await.unwrap();
+
+        let mut request_head = [0u8; 5];
+        server.read_exact(&mut request_head).await.unwrap();
+        assert_eq!(
+            request_head,
+            [0x05, 0x01, 0x00, 0x03, "example.com".len() as u8]
+        );
+        let mut host = vec![0u8; "example.com".len()];
+        server.read_exact(&mut host).await.unwrap();
+        assert_eq!(host, b"example.com");
+        let mut port = [0u8; 2];
+        server.read_exact(&mut port).await.unwrap();
+        assert_eq!(u16::from_be_bytes(port), 443);
+
+        // IPv4 bound-address reply: success, address type 0x01, 4 zero bytes + 2 port bytes.
+        server
+            .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+
+        client_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_socks5_negotiates_username_password_auth() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let proxy = proxy(Some(("user", "pass")));
+
+        let client_task = tokio::spawn(async move {
+            let mut client = client;
+            proxy.connect_socks5(&mut client, "example.com", 443).await
+        });
+
+        let mut greeting = [0u8; 2];
+        server.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [0x05, 0x02]);
+        let mut methods = vec![0u8; greeting[1] as usize];
+        server.read_exact(&mut methods).await.unwrap();
+        assert_eq!(methods, vec![0x00, 0x02]);
+        // Select username/password auth (0x02).
+        server.write_all(&[0x05, 0x02]).await.unwrap();
+
+        let mut creds_head = [0u8; 2];
+        server.read_exact(&mut creds_head).await.unwrap();
+        assert_eq!(creds_head, [0x01, "user".len() as u8]);
+        let mut user = vec![0u8; "user".len()];
+        server.read_exact(&mut user).await.unwrap();
+        assert_eq!(user, b"user");
+        let mut pass_len = [0u8; 1];
+        server.read_exact(&mut pass_len).await.unwrap();
+        assert_eq!(pass_len[0] as usize, "pass".len());
+        let mut pass = vec![0u8; "pass".len()];
+        server.read_exact(&mut pass).await.unwrap();
+        assert_eq!(pass, b"pass");
+        server.write_all(&[0x01, 0x00]).await.unwrap();
+
+        let mut request_head = [0u8; 5 + "example.com".len()];
+        server.read_exact(&mut request_head).await.unwrap();
+        let mut port = [0u8; 2];
+        server.read_exact(&mut port).await.unwrap();
+        assert_eq!(u16::from_be_bytes(port), 443);
+
+        server
+            .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+
+        client_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_socks5_rejects_failure_reply_code() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let proxy = proxy(None);
+
+        let client_task = tokio::spawn(async move {
+            let mut client = client;
+            proxy.connect_socks5(&mut client, "example.com", 443).await
+        });
+
+        let mut greeting = [0u8; 2];
+        server.read_exact(&mut greeting).await.unwrap();
+        let mut methods = vec![0u8; greeting[1] as usize];
+        server.read_exact(&mut methods).await.unwrap();
+        server.write_all(&[0x05, 0x00]).await.unwrap();
+
+        let mut request_head = [0u8; 5 + "example.com".len() + 2];
+        server.read_exact(&mut request_head).await.unwrap();
+
+        // General SOCKS server failure (0x01).
+        server
+            .write_all(&[0x05, 0x01, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+
+        assert!(client_task.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn connect_socks5_rejects_oversized_hostname_without_writing() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let proxy = proxy(None);
+        let host = "a".repeat(256);
+
+        let client_task = tokio::spawn(async move {
+            let mut client = client;
+            proxy.connect_socks5(&mut client, &host, 443).await
+        });
+
+        // The handshake must fail before any bytes are written for the oversized host.
+        assert!(client_task.await.unwrap().is_err());
+
+        let mut buf = [0u8; 1];
+        let read =
+            tokio::time::timeout(std::time::Duration::from_millis(50), server.read(&mut buf)).await;
+        assert!(read.is_err() || matches!(read, Ok(Ok(0))));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::error::Result;
+use http::Request;
+use tungstenite::protocol::WebSocketConfig;
+
+/// The async read/write/connect primitives used by the websocket transports, selected at
+/// compile time via the `tokio-runtime` / `async-std-runtime` / `smol-runtime` features.
+///
+/// Following the approach `async-tungstenite` takes to support multiple executors, the
+/// connect call, the TLS/proxy connector type and the lock guarding transport state are
+/// factored out here so `WebsocketSecureTransport` and its insecure sibling don't need to
+/// know which executor they're running on. Each runtime module sticks to its own crate's
+/// stream and connector types end-to-end rather than mixing `tokio-tungstenite` and
+/// `async-tungstenite` types, since the two are not interchangeable. `tokio-runtime` takes
+/// precedence over `async-std-runtime`, which in turn takes precedence over `smol-runtime`,
+/// if more than one is enabled at once.
+#[cfg(feature = "tokio-runtime")]
+mod tokio_runtime {
+    use super::*;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    /// An async read-write lock compatible with the selected runtime.
+    pub(crate) type Lock<T> = tokio::sync::RwLock<T>;
+
+    /// The TLS/proxy connector type accepted by this runtime's websocket handshake.
+    pub(crate) type Connector = tokio_tungstenite::Connector;
+
+    /// The stream type produced by a successful handshake on this runtime.
+    pub(crate) type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+    /// Connects to `req`, applying `websocket_config` and `tls_config`, using the `tokio`
+    /// reactor.
+    pub(crate) async fn connect(
+        req: Request<()>,
+        websocket_config: Option<WebSocketConfig>,
+        tls_config: Option<Connector>,
+    ) -> Result<WsStream> {
+        let (stream, _) =
+            tokio_tungstenite::connect_async_tls_with_config(req, websocket_config, tls_config)
+                .await?;
+        Ok(stream)
+    }
+
+    /// Dials `target_host:target_port` through `proxy` before performing the TLS/websocket
+    /// handshake over the resulting stream, instead of letting `connect_async_tls_with_config`
+    /// dial the host itself.
+    pub(crate) async fn connect_via_proxy(
+        req: Request<()>,
+        proxy: &crate::asynchronous::proxy::Proxy,
+        target_host: &str,
+        target_port: u16,
+        websocket_config: Option<WebSocketConfig>,
+        tls_config: Option<Connector>,
+    ) -> Result<WsStream> {
+        let tcp_stream = proxy.connect(target_host, target_port).await?;
+        let (stream, _) = tokio_tungstenite::client_async_tls_with_config(
+            req,
+            tcp_stream,
+            websocket_config,
+            tls_config,
+        )
+        .await?;
+        Ok(stream)
+    }
+}
+
+#[cfg(feature = "async-std-runtime")]
+mod async_std_runtime {
+    use super::*;
+    use async_tungstenite::async_std::{connect_async_tls_with_config, ConnectStream};
+    use async_tungstenite::WebSocketStream;
+
+    /// An async read-write lock compatible with the selected runtime.
+    pub(crate) type Lock<T> = async_std::sync::RwLock<T>;
+
+    /// The TLS/proxy connector type accepted by this runtime's websocket handshake.
+    pub(crate) type Connector = async_tungstenite::Connector;
+
+    /// The stream type produced by a successful handshake on this runtime.
+    pub(crate) type WsStream = WebSocketStream<ConnectStream>;
+
+    /// Connects to `req`, applying `websocket_config` and `tls_config`, using the
+    /// `async-std` reactor. Uses `async-tungstenite`'s own connect/stream/connector types
+    /// throughout, since they are distinct from (and not interchangeable with)
+    /// `tokio-tungstenite`'s.
+    pub(crate) async fn connect(
+        req: Request<()>,
+        websocket_config: Option<WebSocketConfig>,
+        tls_config: Option<Connector>,
+    ) -> Result<WsStream> {
+        let (stream, _) = connect_async_tls_with_config(req, websocket_config, tls_config).await?;
+        Ok(stream)
+    }
+
+    /// Dials `target_host:target_port` through `proxy` before performing the TLS/websocket
+    /// handshake over the resulting stream, instead of letting `connect_async_tls_with_config`
+    /// dial the host itself.
+    pub(crate) async fn connect_via_proxy(
+        req: Request<()>,
+        proxy: &crate::asynchronous::proxy::Proxy,
+        target_host: &str,
+        target_port: u16,
+        websocket_config: Option<WebSocketConfig>,
+        tls_config: Option<Connector>,
+    ) -> Result<WsStream> {
+        let tcp_stream = proxy.connect(target_host, target_port).await?;
+        let (stream, _) = async_tungstenite::client_async_tls_with_config(
+            req,
+            tcp_stream,
+            websocket_config,
+            tls_config,
+        )
+        .await?;
+        Ok(stream)
+    }
+}
+
+#[cfg(all(
+    feature = "smol-runtime",
+    not(feature = "tokio-runtime"),
+    not(feature = "async-std-runtime")
+))]
+mod smol_runtime {
+    use super::*;
+    use async_tungstenite::WebSocketStream;
+
+    /// An async read-write lock compatible with the selected runtime.
+    pub(crate) type Lock<T> = async_lock::RwLock<T>;
+
+    /// The TLS/proxy connector type accepted by this runtime's websocket handshake.
+    pub(crate) type Connector = async_tungstenite::Connector;
+
+    /// The stream type produced by a successful handshake on this runtime.
+    pub(crate) type WsStream = WebSocketStream<smol::net::TcpStream>;
+
+    /// Resolves the host/port `req` targets, defaulting to the scheme's well-known port.
+    fn target(req: &Request<()>) -> Result<(String, u16)> {
+        let uri = req.uri();
+        let host = uri
+            .host()
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "request uri has no host")
+            })?
+            .to_string();
+        let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+            Some("wss") | Some("https") => 443,
+            _ => 80,
+        });
+        Ok((host, port))
+    }
+
+    /// Connects to `req`, applying `websocket_config` and `tls_config`, using the `smol`
+    /// reactor. Unlike the `tokio`/`async-std` runtimes, `async-tungstenite` has no
+    /// smol-specific connect helper, so the TCP dial is done by hand before handing the
+    /// stream to `async-tungstenite`'s runtime-agnostic handshake function.
+    pub(crate) async fn connect(
+        req: Request<()>,
+        websocket_config: Option<WebSocketConfig>,
+        tls_config: Option<Connector>,
+    ) -> Result<WsStream> {
+        let (host, port) = target(&req)?;
+        let tcp_stream = smol::net::TcpStream::connect((host.as_str(), port)).await?;
+        let (stream, _) = async_tungstenite::client_async_tls_with_config(
+            req,
+            tcp_stream,
+            websocket_config,
+            tls_config,
+        )
+        .await?;
+        Ok(stream)
+    }
+
+    /// Dials `target_host:target_port` through `proxy` before performing the TLS/websocket
+    /// handshake over the resulting stream, instead of letting the handshake dial the host
+    /// itself.
+    pub(crate) async fn connect_via_proxy(
+        req: Request<()>,
+        proxy: &crate::asynchronous::proxy::Proxy,
+        target_host: &str,
+        target_port: u16,
+        websocket_config: Option<WebSocketConfig>,
+        tls_config: Option<Connector>,
+    ) -> Result<WsStream> {
+        let tcp_stream = proxy.connect(target_host, target_port).await?;
+        let (stream, _) = async_tungstenite::client_async_tls_with_config(
+            req,
+            tcp_stream,
+            websocket_config,
+            tls_config,
+        )
+        .await?;
+        Ok(stream)
+    }
+}
+
+#[cfg(feature = "tokio-runtime")]
+pub(crate) use tokio_runtime::{connect, connect_via_proxy, Connector, Lock, WsStream};
+
+#[cfg(all(feature = "async-std-runtime", not(feature = "tokio-runtime")))]
+pub(crate) use async_std_runtime::{connect, connect_via_proxy, Connector, Lock, WsStream};
+
+#[cfg(all(
+    feature = "smol-runtime",
+    not(feature = "tokio-runtime"),
+    not(feature = "async-std-runtime")
+))]
+pub(crate) use smol_runtime::{connect, connect_via_proxy, Connector, Lock, WsStream};